@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::protocol::{self, Message, SharedWriter, next_request_id};
+use crate::supervisor;
+
+/// In-flight executions started by the parent via `exec_request`, keyed by
+/// execution id, so a later `exec_kill` can reach the right one.
+pub type ExecMap = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<()>>>>;
+
+/// Handle an `exec_request` from the parent: spawn `argv` directly, or
+/// `command` through the supervisor's `/bin/sh`-with-fallback logic, stream
+/// (or capture) its output, and report the final exit status.
+pub async fn handle_request(writer: SharedWriter, execs: ExecMap, payload: Value) {
+    let execution_id = payload
+        .get("execution_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(next_request_id);
+
+    let argv: Option<Vec<String>> = payload.get("argv").and_then(|v| v.as_array()).map(|arr| {
+        arr.iter().filter_map(|v| v.as_str().map(str::to_string)).collect()
+    });
+    let command = payload.get("command").and_then(|v| v.as_str());
+
+    if argv.is_none() && command.is_none() {
+        send_exec_exit(&writer, &execution_id, -1, None, None, Some("Missing 'command' or 'argv'")).await;
+        return;
+    }
+
+    let capture = payload.get("capture").and_then(|v| v.as_bool()).unwrap_or(false);
+    let env_vars = build_exec_env(payload.get("env"));
+
+    // `argv` bypasses the shell (no quoting/injection surface); `command`
+    // falls back to the supervisor's `/bin/sh`-with-fallback logic.
+    let spawn_result = match &argv {
+        Some(argv) => supervisor::spawn_argv(argv, &env_vars).await,
+        None => supervisor::spawn_shell(command.unwrap(), &env_vars).await,
+    };
+    let mut child = match spawn_result {
+        Ok(c) => c,
+        Err(e) => {
+            send_exec_exit(&writer, &execution_id, -1, None, None, Some(&format!("Failed to start: {e}"))).await;
+            return;
+        }
+    };
+
+    let (stdout_tx, stdout_rx) = oneshot::channel();
+    if let Some(stdout) = child.stdout.take() {
+        let w = writer.clone();
+        let id = execution_id.clone();
+        tokio::spawn(async move {
+            let captured = collect_output(&w, &id, "exec_stdout", stdout, capture).await;
+            let _ = stdout_tx.send(captured);
+        });
+    } else {
+        let _ = stdout_tx.send(None);
+    }
+
+    let (stderr_tx, stderr_rx) = oneshot::channel();
+    if let Some(stderr) = child.stderr.take() {
+        let w = writer.clone();
+        let id = execution_id.clone();
+        tokio::spawn(async move {
+            let captured = collect_output(&w, &id, "exec_stderr", stderr, capture).await;
+            let _ = stderr_tx.send(captured);
+        });
+    } else {
+        let _ = stderr_tx.send(None);
+    }
+
+    let (kill_tx, mut kill_rx) = mpsc::unbounded_channel();
+    execs.lock().await.insert(execution_id.clone(), kill_tx);
+
+    let exit_code = tokio::select! {
+        result = child.wait() => {
+            match result {
+                Ok(status) => status.code().unwrap_or(-1),
+                Err(e) => {
+                    execs.lock().await.remove(&execution_id);
+                    send_exec_exit(&writer, &execution_id, -1, None, None, Some(&format!("wait failed: {e}"))).await;
+                    return;
+                }
+            }
+        }
+        _ = kill_rx.recv() => {
+            // Signal the whole process group (spawn_shell/spawn_argv put the
+            // child in its own via process_group(0)), so background children
+            // the executed command may have forked don't survive as orphans.
+            if let Some(pid) = child.id() {
+                if unsafe { libc::kill(-(pid as i32), libc::SIGKILL) } < 0 {
+                    eprintln!("[exec] Failed to kill process group {pid}: {}", std::io::Error::last_os_error());
+                }
+            }
+            let _ = child.wait().await;
+            -1
+        }
+    };
+    execs.lock().await.remove(&execution_id);
+
+    let stdout = stdout_rx.await.ok().flatten();
+    let stderr = stderr_rx.await.ok().flatten();
+    send_exec_exit(&writer, &execution_id, exit_code, stdout, stderr, None).await;
+}
+
+/// Handle an `exec_kill` from the parent, terminating the matching execution if still running.
+pub async fn handle_kill(execs: &ExecMap, payload: &Value) {
+    let Some(execution_id) = payload.get("execution_id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    if let Some(tx) = execs.lock().await.get(execution_id) {
+        let _ = tx.send(());
+    }
+}
+
+fn build_exec_env(overrides: Option<&Value>) -> Vec<(String, String)> {
+    let mut env_vars: Vec<(String, String)> = std::env::vars().collect();
+    let Some(overrides) = overrides.and_then(|v| v.as_object()) else {
+        return env_vars;
+    };
+    for (key, value) in overrides {
+        let Some(value) = value.as_str() else { continue };
+        if let Some(entry) = env_vars.iter_mut().find(|(k, _)| k == key) {
+            entry.1 = value.to_string();
+        } else {
+            env_vars.push((key.clone(), value.to_string()));
+        }
+    }
+    env_vars
+}
+
+/// Stream a child's output line-by-line as `exec_stdout`/`exec_stderr`
+/// messages, or accumulate it for a single capture if `capture` is set.
+async fn collect_output<R: AsyncRead + Unpin>(
+    writer: &SharedWriter,
+    execution_id: &str,
+    msg_type: &str,
+    reader: R,
+    capture: bool,
+) -> Option<String> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut collected = String::new();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if capture {
+            if !collected.is_empty() {
+                collected.push('\n');
+            }
+            collected.push_str(&line);
+        } else {
+            let msg = Message {
+                msg_type: msg_type.to_string(),
+                id: next_request_id(),
+                payload: json!({"execution_id": execution_id, "line": line}),
+            };
+            let _ = protocol::send(writer, &msg).await;
+        }
+    }
+
+    capture.then_some(collected)
+}
+
+async fn send_exec_exit(
+    writer: &SharedWriter,
+    execution_id: &str,
+    exit_code: i32,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    error: Option<&str>,
+) {
+    let mut payload = json!({
+        "execution_id": execution_id,
+        "exit_code": exit_code,
+    });
+    if let Some(stdout) = stdout {
+        payload["stdout"] = json!(stdout);
+    }
+    if let Some(stderr) = stderr {
+        payload["stderr"] = json!(stderr);
+    }
+    if let Some(error) = error {
+        payload["error"] = json!(error);
+    }
+
+    let msg = Message {
+        msg_type: "exec_exit".to_string(),
+        id: next_request_id(),
+        payload,
+    };
+    if let Err(e) = protocol::send(writer, &msg).await {
+        eprintln!("[exec] Failed to send exec_exit: {e}");
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{mpsc, watch, Mutex};
+
+use crate::logging::send_log;
+use crate::protocol::{self, Message, PendingMap, SharedWriter, next_request_id};
+
+/// Maps an open forwarding channel id to the task feeding it data arriving
+/// from the parent, mirroring `PendingMap`'s id-keyed bookkeeping.
+pub type ChannelMap = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<ChannelEvent>>>>;
+
+#[derive(Debug)]
+pub enum ChannelEvent {
+    Data(Vec<u8>),
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    LocalToRemote,
+    RemoteToLocal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ForwardSpec {
+    direction: Direction,
+    protocol: Protocol,
+    bind_addr: String,
+    target_addr: String,
+}
+
+/// Parse `TREZA_FORWARD`, e.g. `L:127.0.0.1:9000->host:5432,R:0.0.0.0:8080->enclave:80`.
+/// A direction may carry an explicit protocol, e.g. `L/udp:127.0.0.1:9000->host:53`;
+/// it defaults to TCP.
+fn parse_specs() -> Vec<ForwardSpec> {
+    env::var("TREZA_FORWARD")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(parse_spec)
+        .collect()
+}
+
+fn parse_spec(entry: &str) -> Option<ForwardSpec> {
+    let (left, target_addr) = entry.split_once("->")?;
+    let (dir_part, bind_addr) = left.split_once(':')?;
+
+    let mut tokens = dir_part.splitn(2, '/');
+    let direction = match tokens.next()? {
+        "L" => Direction::LocalToRemote,
+        "R" => Direction::RemoteToLocal,
+        other => {
+            eprintln!("[forward] Unknown direction '{other}' in spec '{entry}'");
+            return None;
+        }
+    };
+    let protocol = match tokens.next() {
+        Some("udp") => Protocol::Udp,
+        _ => Protocol::Tcp,
+    };
+
+    Some(ForwardSpec {
+        direction,
+        protocol,
+        bind_addr: bind_addr.to_string(),
+        target_addr: target_addr.to_string(),
+    })
+}
+
+/// Start the listeners for every `L` (local-to-remote) entry in `TREZA_FORWARD`.
+/// `R` (remote-to-local) entries are driven by `forward_open` messages from the
+/// parent instead, handled by [`handle_open`].
+pub async fn serve(
+    writer: SharedWriter,
+    pending: PendingMap,
+    channels: ChannelMap,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let specs = parse_specs();
+    if specs.is_empty() {
+        return;
+    }
+
+    let mut tasks = Vec::new();
+    for spec in specs {
+        match spec.direction {
+            Direction::LocalToRemote => match spec.protocol {
+                Protocol::Tcp => tasks.push(tokio::spawn(serve_local_to_remote_tcp(
+                    writer.clone(),
+                    pending.clone(),
+                    channels.clone(),
+                    spec,
+                    shutdown.clone(),
+                ))),
+                Protocol::Udp => tasks.push(tokio::spawn(serve_local_to_remote_udp(
+                    writer.clone(),
+                    channels.clone(),
+                    spec,
+                    shutdown.clone(),
+                ))),
+            },
+            Direction::RemoteToLocal => {
+                // The listener for this direction lives on the parent; the
+                // enclave just waits for forward_open messages it sends.
+                send_log(
+                    &writer,
+                    "info",
+                    &format!(
+                        "Ready for remote-to-local {} forward {} -> {}",
+                        spec.protocol.as_str(),
+                        spec.bind_addr,
+                        spec.target_addr
+                    ),
+                )
+                .await;
+            }
+        }
+    }
+
+    wait_shutdown(&mut shutdown).await;
+    for task in tasks {
+        task.abort();
+    }
+}
+
+async fn serve_local_to_remote_tcp(
+    writer: SharedWriter,
+    pending: PendingMap,
+    channels: ChannelMap,
+    spec: ForwardSpec,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let listener = match TcpListener::bind(&spec.bind_addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            send_log(&writer, "error", &format!("Failed to bind forward listener {}: {e}", spec.bind_addr)).await;
+            return;
+        }
+    };
+    send_log(&writer, "info", &format!("Forwarding TCP {} -> {}", spec.bind_addr, spec.target_addr)).await;
+
+    loop {
+        tokio::select! {
+            result = listener.accept() => {
+                match result {
+                    Ok((stream, _)) => {
+                        let w = writer.clone();
+                        let p = pending.clone();
+                        let c = channels.clone();
+                        let target = spec.target_addr.clone();
+                        tokio::spawn(async move {
+                            splice_local_to_remote(w, p, c, stream, target).await;
+                        });
+                    }
+                    Err(e) => eprintln!("[forward] Accept error on {}: {e}", spec.bind_addr),
+                }
+            }
+            _ = wait_shutdown(&mut shutdown) => break,
+        }
+    }
+}
+
+async fn splice_local_to_remote(
+    writer: SharedWriter,
+    pending: PendingMap,
+    channels: ChannelMap,
+    mut stream: TcpStream,
+    target: String,
+) {
+    let channel_id = next_request_id();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    channels.lock().await.insert(channel_id.clone(), tx);
+
+    let open = protocol::request(
+        &writer,
+        &pending,
+        "forward_open",
+        json!({"channel_id": channel_id, "protocol": "tcp", "target": target}),
+        30,
+    )
+    .await;
+
+    if let Err(e) = open {
+        eprintln!("[forward] Failed to open channel to {target}: {e}");
+        channels.lock().await.remove(&channel_id);
+        return;
+    }
+
+    let (mut read_half, mut write_half) = stream.split();
+    let mut buf = [0u8; 16 * 1024];
+
+    loop {
+        tokio::select! {
+            result = read_half.read(&mut buf) => {
+                match result {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        let msg = Message {
+                            msg_type: "forward_data".to_string(),
+                            id: next_request_id(),
+                            payload: json!({"channel_id": channel_id, "data": encode(&buf[..n])}),
+                        };
+                        if protocol::send(&writer, &msg).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            event = rx.recv() => {
+                match event {
+                    Some(ChannelEvent::Data(data)) => {
+                        if write_half.write_all(&data).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(ChannelEvent::Closed) | None => break,
+                }
+            }
+        }
+    }
+
+    channels.lock().await.remove(&channel_id);
+    let _ = protocol::send(
+        &writer,
+        &Message {
+            msg_type: "forward_close".to_string(),
+            id: next_request_id(),
+            payload: json!({"channel_id": channel_id}),
+        },
+    )
+    .await;
+}
+
+async fn serve_local_to_remote_udp(
+    writer: SharedWriter,
+    channels: ChannelMap,
+    spec: ForwardSpec,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let socket = match UdpSocket::bind(&spec.bind_addr).await {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            send_log(&writer, "error", &format!("Failed to bind UDP forward listener {}: {e}", spec.bind_addr)).await;
+            return;
+        }
+    };
+    send_log(&writer, "info", &format!("Forwarding UDP {} -> {}", spec.bind_addr, spec.target_addr)).await;
+
+    let peers: Arc<Mutex<HashMap<SocketAddr, String>>> = Arc::new(Mutex::new(HashMap::new()));
+    let mut buf = [0u8; 16 * 1024];
+
+    loop {
+        tokio::select! {
+            result = socket.recv_from(&mut buf) => {
+                match result {
+                    Ok((n, peer)) => {
+                        let existing = peers.lock().await.get(&peer).cloned();
+                        let channel_id = match existing {
+                            Some(id) => id,
+                            None => {
+                                let id = next_request_id();
+                                let (tx, mut rx) = mpsc::unbounded_channel();
+                                channels.lock().await.insert(id.clone(), tx);
+                                peers.lock().await.insert(peer, id.clone());
+
+                                let sock = socket.clone();
+                                let cid = id.clone();
+                                let channels_cleanup = channels.clone();
+                                let peers_cleanup = peers.clone();
+                                tokio::spawn(async move {
+                                    while let Some(event) = rx.recv().await {
+                                        match event {
+                                            ChannelEvent::Data(data) => {
+                                                let _ = sock.send_to(&data, peer).await;
+                                            }
+                                            ChannelEvent::Closed => break,
+                                        }
+                                    }
+                                    channels_cleanup.lock().await.remove(&cid);
+                                    peers_cleanup.lock().await.remove(&peer);
+                                });
+                                id
+                            }
+                        };
+
+                        let msg = Message {
+                            msg_type: "forward_data".to_string(),
+                            id: next_request_id(),
+                            payload: json!({
+                                "channel_id": channel_id,
+                                "protocol": "udp",
+                                "target": spec.target_addr,
+                                "data": encode(&buf[..n]),
+                            }),
+                        };
+                        let _ = protocol::send(&writer, &msg).await;
+                    }
+                    Err(e) => eprintln!("[forward] UDP recv error on {}: {e}", spec.bind_addr),
+                }
+            }
+            _ = wait_shutdown(&mut shutdown) => break,
+        }
+    }
+}
+
+/// Handle an unsolicited `forward_open` from the parent (remote-to-local
+/// direction): connect locally to the requested target and splice bytes.
+/// Branches on the message's `protocol` field (defaulting to TCP), mirroring the
+/// `L`-direction listeners' TCP/UDP split.
+pub async fn handle_open(writer: SharedWriter, channels: ChannelMap, payload: Value) {
+    let channel_id = match payload.get("channel_id").and_then(|v| v.as_str()) {
+        Some(id) => id.to_string(),
+        None => return,
+    };
+    let target = match payload.get("target").and_then(|v| v.as_str()) {
+        Some(t) => t.to_string(),
+        None => return,
+    };
+
+    match payload.get("protocol").and_then(|v| v.as_str()) {
+        Some("udp") => handle_open_udp(writer, channels, channel_id, target).await,
+        _ => handle_open_tcp(writer, channels, channel_id, target).await,
+    }
+}
+
+async fn handle_open_tcp(writer: SharedWriter, channels: ChannelMap, channel_id: String, target: String) {
+    let mut stream = match TcpStream::connect(&target).await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[forward] Failed to connect to {target} for channel {channel_id}: {e}");
+            send_forward_close(&writer, &channel_id).await;
+            return;
+        }
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    channels.lock().await.insert(channel_id.clone(), tx);
+
+    tokio::spawn(async move {
+        let (mut read_half, mut write_half) = stream.split();
+        let mut buf = [0u8; 16 * 1024];
+
+        loop {
+            tokio::select! {
+                result = read_half.read(&mut buf) => {
+                    match result {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            let msg = Message {
+                                msg_type: "forward_data".to_string(),
+                                id: next_request_id(),
+                                payload: json!({"channel_id": channel_id, "data": encode(&buf[..n])}),
+                            };
+                            if protocol::send(&writer, &msg).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                event = rx.recv() => {
+                    match event {
+                        Some(ChannelEvent::Data(data)) => {
+                            if write_half.write_all(&data).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(ChannelEvent::Closed) | None => break,
+                    }
+                }
+            }
+        }
+
+        channels.lock().await.remove(&channel_id);
+    });
+}
+
+/// Same as [`handle_open_tcp`], but for a `protocol: "udp"` forward: connects
+/// a UDP socket to the single target peer and relays datagrams, mirroring
+/// [`serve_local_to_remote_udp`]'s bind/recv/send pattern.
+async fn handle_open_udp(writer: SharedWriter, channels: ChannelMap, channel_id: String, target: String) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[forward] Failed to bind UDP socket for channel {channel_id}: {e}");
+            send_forward_close(&writer, &channel_id).await;
+            return;
+        }
+    };
+    if let Err(e) = socket.connect(&target).await {
+        eprintln!("[forward] Failed to connect UDP socket to {target} for channel {channel_id}: {e}");
+        send_forward_close(&writer, &channel_id).await;
+        return;
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    channels.lock().await.insert(channel_id.clone(), tx);
+
+    tokio::spawn(async move {
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            tokio::select! {
+                result = socket.recv(&mut buf) => {
+                    match result {
+                        Ok(n) => {
+                            let msg = Message {
+                                msg_type: "forward_data".to_string(),
+                                id: next_request_id(),
+                                payload: json!({"channel_id": channel_id, "data": encode(&buf[..n])}),
+                            };
+                            if protocol::send(&writer, &msg).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("[forward] UDP recv error for channel {channel_id}: {e}");
+                            break;
+                        }
+                    }
+                }
+                event = rx.recv() => {
+                    match event {
+                        Some(ChannelEvent::Data(data)) => {
+                            if socket.send(&data).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(ChannelEvent::Closed) | None => break,
+                    }
+                }
+            }
+        }
+
+        channels.lock().await.remove(&channel_id);
+    });
+}
+
+async fn send_forward_close(writer: &SharedWriter, channel_id: &str) {
+    let _ = protocol::send(
+        writer,
+        &Message {
+            msg_type: "forward_close".to_string(),
+            id: next_request_id(),
+            payload: json!({"channel_id": channel_id}),
+        },
+    )
+    .await;
+}
+
+/// Route a `forward_data` message from the parent to the local half of its channel.
+pub async fn handle_data(channels: &ChannelMap, payload: &Value) {
+    let Some(channel_id) = payload.get("channel_id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    let Some(data) = payload.get("data").and_then(|v| v.as_str()).and_then(decode) else {
+        return;
+    };
+
+    if let Some(tx) = channels.lock().await.get(channel_id) {
+        let _ = tx.send(ChannelEvent::Data(data));
+    }
+}
+
+/// Route a `forward_close` message from the parent, tearing down the local channel.
+pub async fn handle_close(channels: &ChannelMap, payload: &Value) {
+    let Some(channel_id) = payload.get("channel_id").and_then(|v| v.as_str()) else {
+        return;
+    };
+
+    if let Some(tx) = channels.lock().await.remove(channel_id) {
+        let _ = tx.send(ChannelEvent::Closed);
+    }
+}
+
+async fn wait_shutdown(rx: &mut watch::Receiver<bool>) {
+    while !*rx.borrow() {
+        if rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+
+        out.push(ALPHABET[(b0 >> 2) as usize] as char);
+        out.push(ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize] as char);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(((b1 & 0x0f) << 2) | (b2 >> 6)) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if chunk.len() > 2 { ALPHABET[(b2 & 0x3f) as usize] as char } else { '=' });
+    }
+    out
+}
+
+fn decode(data: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u8> {
+        match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let bytes: Vec<u8> = data.bytes().filter(|&b| b != b'=').collect();
+    let mut out = Vec::with_capacity(bytes.len() * 3 / 4);
+    for chunk in bytes.chunks(4) {
+        let vals = chunk.iter().map(|&b| value(b)).collect::<Option<Vec<u8>>>()?;
+        out.push((vals[0] << 2) | (vals.get(1).copied().unwrap_or(0) >> 4));
+        if vals.len() > 2 {
+            out.push((vals[1] << 4) | (vals[2] >> 2));
+        }
+        if vals.len() > 3 {
+            out.push((vals[2] << 6) | vals[3]);
+        }
+    }
+    Some(out)
+}
@@ -1,8 +1,12 @@
+mod exec;
+mod forward;
 mod health;
 mod http_proxy;
 mod kms_proxy;
 mod logging;
+mod probe;
 mod protocol;
+mod pty;
 mod supervisor;
 mod vsock;
 
@@ -13,7 +17,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use serde_json::json;
-use tokio::sync::{watch, Mutex};
+use tokio::sync::{mpsc, watch, Mutex};
 
 use protocol::{PendingMap, SharedWriter};
 
@@ -54,12 +58,45 @@ async fn main() {
         std::process::exit(1);
     }
 
-    // Start response dispatcher
+    // Start response dispatcher; unsolicited parent commands flow through `inbound_rx`.
+    let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
     {
         let pending = pending.clone();
         let shutdown_rx = shutdown_rx.clone();
         tokio::spawn(async move {
-            protocol::response_dispatcher(reader, pending, shutdown_rx).await;
+            protocol::response_dispatcher(reader, pending, inbound_tx, shutdown_rx).await;
+        });
+    }
+
+    // Active PTY session (if the workload is running in PTY mode), shared with
+    // the inbound command router so parent-side resize events can reach it.
+    let pty_handle: supervisor::PtyHandle = Arc::new(Mutex::new(None));
+
+    // Open port-forwarding channels, keyed by channel id.
+    let forward_channels: forward::ChannelMap = Arc::new(Mutex::new(HashMap::new()));
+
+    // In-flight exec executions, keyed by execution id.
+    let execs: exec::ExecMap = Arc::new(Mutex::new(HashMap::new()));
+
+    // Route unsolicited parent commands (resize, forwarded data, exec, ...) to their handlers.
+    {
+        let pty_handle = pty_handle.clone();
+        let forward_channels = forward_channels.clone();
+        let execs = execs.clone();
+        let w = writer.clone();
+        tokio::spawn(async move {
+            handle_inbound_commands(inbound_rx, pty_handle, forward_channels, execs, w).await;
+        });
+    }
+
+    // Start port-forwarding listeners (TREZA_FORWARD)
+    {
+        let w = writer.clone();
+        let p = pending.clone();
+        let c = forward_channels.clone();
+        let rx = shutdown_rx.clone();
+        tokio::spawn(async move {
+            forward::serve(w, p, c, rx).await;
         });
     }
 
@@ -132,6 +169,7 @@ async fn main() {
             &user_cmd,
             &workload_type,
             health_interval,
+            pty_handle,
             shutdown_rx.clone(),
             shutdown_tx,
         )
@@ -181,8 +219,52 @@ async fn send_handshake(writer: &SharedWriter, enclave_id: &str) -> std::io::Res
         payload: json!({
             "enclave_id": enclave_id,
             "protocol_version": "2.0",
-            "capabilities": ["http_proxy", "kms_proxy", "log_stream", "health"],
+            "capabilities": ["http_proxy", "kms_proxy", "log_stream", "health", "port_forward", "exec"],
         }),
     };
     protocol::send(writer, &msg).await
 }
+
+/// Dispatch unsolicited messages from the parent that aren't replies to a
+/// pending request, e.g. PTY window-size updates or forwarded channel data.
+async fn handle_inbound_commands(
+    mut inbound: protocol::InboundReceiver,
+    pty_handle: supervisor::PtyHandle,
+    forward_channels: forward::ChannelMap,
+    execs: exec::ExecMap,
+    writer: SharedWriter,
+) {
+    while let Some(msg) = inbound.recv().await {
+        match msg.msg_type.as_str() {
+            "pty_resize" => {
+                supervisor::handle_pty_resize(&pty_handle, &msg.payload).await;
+            }
+            "forward_open" => {
+                let w = writer.clone();
+                let forward_channels = forward_channels.clone();
+                tokio::spawn(async move {
+                    forward::handle_open(w, forward_channels, msg.payload).await;
+                });
+            }
+            "forward_data" => {
+                forward::handle_data(&forward_channels, &msg.payload).await;
+            }
+            "forward_close" => {
+                forward::handle_close(&forward_channels, &msg.payload).await;
+            }
+            "exec_request" => {
+                let w = writer.clone();
+                let execs = execs.clone();
+                tokio::spawn(async move {
+                    exec::handle_request(w, execs, msg.payload).await;
+                });
+            }
+            "exec_kill" => {
+                exec::handle_kill(&execs, &msg.payload).await;
+            }
+            other => {
+                eprintln!("[enclave-proxy] Unhandled inbound message type '{other}'");
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Per-attempt timeout so a hung socket can't stall the supervision loop.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A readiness/liveness probe for a service workload, configured via
+/// `TREZA_READINESS_TCP` (plain connect) or `TREZA_READINESS_HTTP` (GET, 2xx required).
+#[derive(Debug, Clone)]
+pub enum Probe {
+    Tcp(String),
+    Http(String),
+}
+
+impl Probe {
+    /// Read the configured probe from the environment, if any.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(addr) = env::var("TREZA_READINESS_TCP") {
+            if !addr.is_empty() {
+                return Some(Probe::Tcp(addr));
+            }
+        }
+        if let Ok(url) = env::var("TREZA_READINESS_HTTP") {
+            if !url.is_empty() {
+                return Some(Probe::Http(url));
+            }
+        }
+        None
+    }
+
+    /// Run one probe attempt, bounded by [`PROBE_TIMEOUT`]. Returns `false` on
+    /// any error or timeout rather than propagating it.
+    pub async fn check(&self) -> bool {
+        matches!(tokio::time::timeout(PROBE_TIMEOUT, self.attempt()).await, Ok(Ok(true)))
+    }
+
+    async fn attempt(&self) -> std::io::Result<bool> {
+        match self {
+            Probe::Tcp(addr) => {
+                TcpStream::connect(addr).await?;
+                Ok(true)
+            }
+            Probe::Http(url) => http_get_ok(url).await,
+        }
+    }
+}
+
+async fn http_get_ok(url: &str) -> std::io::Result<bool> {
+    let (host, path) = split_url(url)?;
+    let mut stream = TcpStream::connect(&host).await?;
+
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await?;
+    let response = String::from_utf8_lossy(&buf);
+    let status: u16 = response
+        .lines()
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    Ok((200..300).contains(&status))
+}
+
+fn split_url(url: &str) -> std::io::Result<(String, String)> {
+    let without_scheme = url.strip_prefix("http://").ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unsupported readiness URL: {url}"))
+    })?;
+
+    let (host_port, path) = match without_scheme.split_once('/') {
+        Some((h, p)) => (h, format!("/{p}")),
+        None => (without_scheme, "/".to_string()),
+    };
+    let host = if host_port.contains(':') { host_port.to_string() } else { format!("{host_port}:80") };
+    Ok((host, path))
+}
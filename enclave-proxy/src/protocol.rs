@@ -6,7 +6,7 @@ use std::sync::Arc;
 use tokio::io;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
-use tokio::sync::{oneshot, Mutex};
+use tokio::sync::{mpsc, oneshot, Mutex};
 
 static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
 
@@ -29,6 +29,12 @@ pub type SharedWriter = Arc<Mutex<OwnedWriteHalf>>;
 /// Map of request IDs to oneshot senders waiting for responses.
 pub type PendingMap = Arc<Mutex<HashMap<String, oneshot::Sender<Message>>>>;
 
+/// Sender half for messages from the parent that aren't replies to a pending
+/// request (e.g. PTY resize notifications, exec commands).
+pub type InboundSender = mpsc::UnboundedSender<Message>;
+/// Receiver half of [`InboundSender`].
+pub type InboundReceiver = mpsc::UnboundedReceiver<Message>;
+
 /// Split a TcpStream (vsock) into a shared writer and the reader half.
 pub fn split_connection(stream: TcpStream) -> (SharedWriter, OwnedReadHalf) {
     let (read, write) = stream.into_split();
@@ -75,10 +81,12 @@ pub async fn recv(reader: &mut OwnedReadHalf) -> io::Result<Option<Message>> {
 }
 
 /// Response dispatcher: reads messages from parent and routes responses
-/// to pending request waiters by ID.
+/// to pending request waiters by ID. Messages that don't match a pending
+/// request (unsolicited commands from the parent) are forwarded on `inbound`.
 pub async fn response_dispatcher(
     mut reader: OwnedReadHalf,
     pending: PendingMap,
+    inbound: InboundSender,
     shutdown: tokio::sync::watch::Receiver<bool>,
 ) {
     loop {
@@ -87,8 +95,14 @@ pub async fn response_dispatcher(
                 match result {
                     Ok(Some(msg)) => {
                         let mut map = pending.lock().await;
-                        if let Some(sender) = map.remove(&msg.id) {
-                            let _ = sender.send(msg);
+                        match map.remove(&msg.id) {
+                            Some(sender) => {
+                                let _ = sender.send(msg);
+                            }
+                            None => {
+                                drop(map);
+                                let _ = inbound.send(msg);
+                            }
                         }
                     }
                     Ok(None) => {
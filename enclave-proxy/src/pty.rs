@@ -0,0 +1,157 @@
+use std::ffi::CStr;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::process::Stdio;
+
+use tokio::io::unix::AsyncFd;
+use tokio::process::unix::CommandExt;
+use tokio::process::{Child, Command};
+
+/// A PTY master, paired with a child process attached to the slave end.
+/// Reads/writes on the master see the merged stdout/stderr of the child,
+/// exactly as a real terminal would.
+pub struct PtySession {
+    master: AsyncFd<OwnedFd>,
+}
+
+impl PtySession {
+    /// Read a chunk of output from the PTY master.
+    pub async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.master.readable().await?;
+            let result = guard.try_io(|inner| {
+                let fd = inner.as_raw_fd();
+                let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            match result {
+                Ok(res) => return res,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Apply a new terminal window size, as reported by the parent.
+    pub fn resize(&self, cols: u16, rows: u16) -> io::Result<()> {
+        let ws = libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let ret = unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &ws) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Open a PTY master/slave pair and spawn `user_cmd` with the slave end wired
+/// to stdin/stdout/stderr, in its own session so the slave becomes its
+/// controlling terminal.
+pub fn spawn(user_cmd: &str, env_vars: &[(String, String)]) -> io::Result<(Child, PtySession)> {
+    let master_fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if master_fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Keep the master fd out of the child: without this it's inherited across
+    // exec and hands the spawned command (and anything it forks) a handle to
+    // its own controlling terminal's master side.
+    if unsafe { libc::fcntl(master_fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(master_fd) };
+        return Err(err);
+    }
+
+    let setup = || -> io::Result<()> {
+        if unsafe { libc::grantpt(master_fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::unlockpt(master_fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    if let Err(e) = setup() {
+        unsafe { libc::close(master_fd) };
+        return Err(e);
+    }
+
+    let mut name_buf = [0i8; 64];
+    if unsafe { libc::ptsname_r(master_fd, name_buf.as_mut_ptr(), name_buf.len()) } != 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(master_fd) };
+        return Err(err);
+    }
+    let slave_path = unsafe { CStr::from_ptr(name_buf.as_ptr()) };
+
+    let slave_fd = unsafe { libc::open(slave_path.as_ptr(), libc::O_RDWR | libc::O_NOCTTY) };
+    if slave_fd < 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(master_fd) };
+        return Err(err);
+    }
+
+    let stdout_fd = unsafe { libc::dup(slave_fd) };
+    if stdout_fd < 0 {
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(slave_fd);
+            libc::close(master_fd);
+        }
+        return Err(err);
+    }
+
+    let stderr_fd = unsafe { libc::dup(slave_fd) };
+    if stderr_fd < 0 {
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(stdout_fd);
+            libc::close(slave_fd);
+            libc::close(master_fd);
+        }
+        return Err(err);
+    }
+
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c")
+        .arg(user_cmd)
+        .envs(env_vars.iter().cloned())
+        .stdin(unsafe { Stdio::from_raw_fd(slave_fd) })
+        .stdout(unsafe { Stdio::from_raw_fd(stdout_fd) })
+        .stderr(unsafe { Stdio::from_raw_fd(stderr_fd) });
+
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            unsafe { libc::close(master_fd) };
+            return Err(e);
+        }
+    };
+
+    let master = AsyncFd::new(unsafe { OwnedFd::from_raw_fd(master_fd) })?;
+    Ok((child, PtySession { master }))
+}
+
+/// Resolve the `TERM` value to propagate into a PTY-backed child's environment.
+pub fn term_value() -> String {
+    std::env::var("TERM").ok().filter(|v| !v.is_empty()).unwrap_or_else(|| "xterm-256color".to_string())
+}
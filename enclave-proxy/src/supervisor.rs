@@ -1,13 +1,103 @@
 use std::env;
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
-use serde_json::json;
+use serde_json::{json, Value};
 use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::unix::CommandExt;
 use tokio::process::Command;
-use tokio::sync::watch;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
 
 use crate::logging::send_log;
+use crate::probe::Probe;
 use crate::protocol::{self, Message, PendingMap, SharedWriter, next_request_id};
+use crate::pty::{self, PtySession};
+
+/// Holds the active PTY session (if the workload is running in PTY mode), so
+/// that inbound resize events from the parent can reach it.
+pub type PtyHandle = Arc<Mutex<Option<Arc<PtySession>>>>;
+
+/// Minimum uptime before a crash-loop attempt counter resets to zero.
+const RESTART_STABILITY_WINDOW: Duration = Duration::from_secs(30);
+/// Upper bound on the exponential restart backoff delay.
+const RESTART_BACKOFF_CAP_MS: u64 = 30_000;
+/// How long to retry the readiness probe before reporting `unready`.
+const READINESS_GRACE: Duration = Duration::from_secs(60);
+/// Upper bound on how long we wait for a stream-draining task to finish.
+const STREAM_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn liveness_failure_threshold() -> u32 {
+    env::var("TREZA_LIVENESS_FAILURES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3)
+}
+
+/// How long to wait after SIGTERM before escalating to SIGKILL.
+fn shutdown_grace() -> Duration {
+    Duration::from_secs(
+        env::var("TREZA_SHUTDOWN_GRACE_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(10),
+    )
+}
+
+/// Whether a terminated child exited on its own after SIGTERM, or had to be
+/// force-killed once the grace period elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TerminationKind {
+    Graceful,
+    Forced,
+}
+
+impl TerminationKind {
+    fn health_status(self) -> &'static str {
+        match self {
+            TerminationKind::Graceful => "stopped",
+            TerminationKind::Forced => "killed",
+        }
+    }
+}
+
+/// Restart behavior for service/daemon workloads, driven by `TREZA_RESTART_POLICY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RestartPolicy {
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    fn from_env() -> Self {
+        match env::var("TREZA_RESTART_POLICY").unwrap_or_default().as_str() {
+            "always" => RestartPolicy::Always,
+            "on-failure" => RestartPolicy::OnFailure,
+            _ => RestartPolicy::Never,
+        }
+    }
+
+    fn allows(self, exit_code: Option<i32>) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => exit_code != Some(0),
+        }
+    }
+}
+
+fn restart_max() -> u32 {
+    env::var("TREZA_RESTART_MAX")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+}
+
+fn restart_backoff_base_ms() -> u64 {
+    env::var("TREZA_RESTART_BACKOFF_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000)
+}
 
 /// Determine the user command from environment variables.
 /// Checks TREZA_USER_CMD first, then combines TREZA_USER_ENTRYPOINT + TREZA_USER_CMD_ARGS.
@@ -29,35 +119,87 @@ pub fn resolve_user_command() -> Option<String> {
     }
 }
 
+/// Whether the user process should be launched attached to a PTY instead of pipes.
+fn pty_enabled(workload_type: &str) -> bool {
+    workload_type == "pty" || env::var("TREZA_USER_PTY").as_deref() == Ok("1")
+}
+
 /// Build the environment for the user process with proxy settings.
-fn build_user_env() -> Vec<(String, String)> {
+fn build_user_env(pty_mode: bool) -> Vec<(String, String)> {
     let mut env_vars: Vec<(String, String)> = env::vars().collect();
 
     let proxy_url = "http://127.0.0.1:3128";
     let kms_url = "http://127.0.0.1:8000";
     let no_proxy = "127.0.0.1,localhost";
 
-    let overrides = [
-        ("HTTP_PROXY", proxy_url),
-        ("HTTPS_PROXY", proxy_url),
-        ("http_proxy", proxy_url),
-        ("https_proxy", proxy_url),
-        ("TREZA_KMS_ENDPOINT", kms_url),
-        ("NO_PROXY", no_proxy),
-        ("no_proxy", no_proxy),
+    let mut overrides = vec![
+        ("HTTP_PROXY", proxy_url.to_string()),
+        ("HTTPS_PROXY", proxy_url.to_string()),
+        ("http_proxy", proxy_url.to_string()),
+        ("https_proxy", proxy_url.to_string()),
+        ("TREZA_KMS_ENDPOINT", kms_url.to_string()),
+        ("NO_PROXY", no_proxy.to_string()),
+        ("no_proxy", no_proxy.to_string()),
     ];
+    if pty_mode {
+        overrides.push(("TERM", pty::term_value()));
+    }
 
     for (key, val) in &overrides {
         if let Some(entry) = env_vars.iter_mut().find(|(k, _)| k == key) {
-            entry.1 = val.to_string();
+            entry.1 = val.clone();
         } else {
-            env_vars.push((key.to_string(), val.to_string()));
+            env_vars.push((key.to_string(), val.clone()));
         }
     }
 
     env_vars
 }
 
+/// A spawned child paired with the background tasks streaming its output, so
+/// shutdown can wait for both the process and its log drains to finish.
+struct ManagedChild {
+    child: tokio::process::Child,
+    stream_tasks: Vec<JoinHandle<()>>,
+}
+
+/// Send SIGTERM to the child's process group and wait up to
+/// [`shutdown_grace`] for it to exit before escalating to SIGKILL.
+async fn graceful_terminate(writer: &SharedWriter, child: &mut tokio::process::Child, what: &str) -> TerminationKind {
+    let Some(pid) = child.id() else {
+        // Already reaped; nothing left to signal.
+        return TerminationKind::Graceful;
+    };
+
+    if unsafe { libc::kill(-(pid as i32), libc::SIGTERM) } < 0 {
+        send_log(writer, "warn", &format!("Failed to send SIGTERM to {what}: {}", io::Error::last_os_error())).await;
+    }
+
+    let grace = shutdown_grace();
+    match tokio::time::timeout(grace, child.wait()).await {
+        Ok(_) => TerminationKind::Graceful,
+        Err(_) => {
+            send_log(
+                writer,
+                "warn",
+                &format!("{what} did not exit within {}s of SIGTERM, sending SIGKILL", grace.as_secs()),
+            )
+            .await;
+            let _ = child.kill().await;
+            let _ = child.wait().await;
+            TerminationKind::Forced
+        }
+    }
+}
+
+/// Wait for the stream-draining tasks to flush whatever output they already
+/// buffered, bounded by [`STREAM_DRAIN_TIMEOUT`] so a stuck reader can't hang shutdown.
+async fn drain_streams(stream_tasks: Vec<JoinHandle<()>>) {
+    for task in stream_tasks {
+        let _ = tokio::time::timeout(STREAM_DRAIN_TIMEOUT, task).await;
+    }
+}
+
 /// Supervise the user process: spawn it, stream output, handle lifecycle
 /// based on workload type (batch, service, daemon).
 pub async fn run(
@@ -66,51 +208,192 @@ pub async fn run(
     user_cmd: &str,
     workload_type: &str,
     health_interval: u64,
+    pty_handle: PtyHandle,
     mut shutdown: watch::Receiver<bool>,
     shutdown_tx: watch::Sender<bool>,
 ) {
-    let env_vars = build_user_env();
+    let pty_mode = pty_enabled(workload_type);
+    let env_vars = build_user_env(pty_mode);
 
     send_log(&writer, "info", &format!("Starting user application: {user_cmd}")).await;
 
-    // Use /bin/sh if available, fall back to direct execution
-    let mut child = match Command::new("/bin/sh")
+    let mut managed = match spawn_and_attach(&writer, user_cmd, &env_vars, pty_mode, &pty_handle).await {
+        Some(c) => c,
+        None => return,
+    };
+
+    match workload_type {
+        "batch" | "pty" => run_batch(&writer, &mut managed, &mut shutdown).await,
+        "service" | "daemon" => {
+            run_service(
+                &writer,
+                &mut managed,
+                user_cmd,
+                &env_vars,
+                pty_mode,
+                &pty_handle,
+                health_interval,
+                &mut shutdown,
+            )
+            .await
+        }
+        other => {
+            send_log(&writer, "warn", &format!("Unknown workload type '{other}', treating as batch")).await;
+            run_batch(&writer, &mut managed, &mut shutdown).await;
+        }
+    }
+
+    // Ensure shutdown is signaled
+    let _ = shutdown_tx.send(true);
+}
+
+/// Spawn the user command either via PTY or plain pipes, attaching whichever
+/// output stream applies, and registering the PTY session (if any) so inbound
+/// resize events can reach it.
+async fn spawn_and_attach(
+    writer: &SharedWriter,
+    user_cmd: &str,
+    env_vars: &[(String, String)],
+    pty_mode: bool,
+    pty_handle: &PtyHandle,
+) -> Option<ManagedChild> {
+    if pty_mode {
+        match pty::spawn(user_cmd, env_vars) {
+            Ok((child, session)) => {
+                let session = Arc::new(session);
+                *pty_handle.lock().await = Some(session.clone());
+                let stream_tasks = vec![attach_pty_stream(writer, session)];
+                Some(ManagedChild { child, stream_tasks })
+            }
+            Err(e) => {
+                send_log(writer, "error", &format!("Failed to allocate PTY: {e}")).await;
+                None
+            }
+        }
+    } else {
+        let mut child = spawn_child(writer, user_cmd, env_vars).await?;
+        let stream_tasks = attach_streams(writer, &mut child);
+        Some(ManagedChild { child, stream_tasks })
+    }
+}
+
+/// Stream the merged PTY master output to the parent as log messages.
+fn attach_pty_stream(writer: &SharedWriter, session: Arc<PtySession>) -> JoinHandle<()> {
+    let w = writer.clone();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 4096];
+        loop {
+            match session.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => {
+                    let chunk = String::from_utf8_lossy(&buf[..n]);
+                    for line in chunk.split('\n') {
+                        if !line.is_empty() {
+                            send_log(&w, "app", line).await;
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("[supervisor] PTY read error: {e}");
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Handle a `pty_resize` message from the parent by applying the new window
+/// size to the active PTY session, if any.
+pub async fn handle_pty_resize(pty_handle: &PtyHandle, payload: &Value) {
+    let cols = payload.get("cols").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
+    let rows = payload.get("rows").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
+    if cols == 0 || rows == 0 {
+        return;
+    }
+
+    if let Some(session) = pty_handle.lock().await.as_ref() {
+        if let Err(e) = session.resize(cols, rows) {
+            eprintln!("[supervisor] Failed to resize PTY: {e}");
+        }
+    }
+}
+
+/// Spawn a shell command with piped stdout/stderr, falling back to direct
+/// execution if `/bin/sh` is unavailable (e.g. scratch images). Shared by the
+/// supervisor and the parent-driven exec facility.
+pub async fn spawn_shell(
+    user_cmd: &str,
+    env_vars: &[(String, String)],
+) -> std::io::Result<tokio::process::Child> {
+    match Command::new("/bin/sh")
         .arg("-c")
         .arg(user_cmd)
-        .envs(env_vars.clone())
+        .envs(env_vars.iter().cloned())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
+        .process_group(0)
         .spawn()
     {
-        Ok(c) => c,
+        Ok(c) => Ok(c),
         Err(e) => {
-            // If /bin/sh is not available (scratch image), try direct execution
             eprintln!("[supervisor] /bin/sh failed ({e}), trying direct execution");
             let parts: Vec<&str> = user_cmd.split_whitespace().collect();
-            if parts.is_empty() {
-                send_log(&writer, "error", "Empty user command").await;
-                return;
-            }
-            match Command::new(parts[0])
+            let Some(program) = parts.first() else {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Empty command"));
+            };
+            Command::new(program)
                 .args(&parts[1..])
-                .envs(env_vars)
+                .envs(env_vars.iter().cloned())
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped())
+                .process_group(0)
                 .spawn()
-            {
-                Ok(c) => c,
-                Err(e2) => {
-                    send_log(&writer, "error", &format!("Failed to start user app: {e2}")).await;
-                    return;
-                }
-            }
         }
+    }
+}
+
+/// Spawn a command directly from an argv vector, bypassing `/bin/sh -c` so
+/// the caller isn't exposed to shell-quoting/injection issues. Used by the
+/// parent-driven exec facility when it's given `argv` instead of `command`.
+pub async fn spawn_argv(
+    argv: &[String],
+    env_vars: &[(String, String)],
+) -> std::io::Result<tokio::process::Child> {
+    let Some(program) = argv.first() else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Empty argv"));
     };
+    Command::new(program)
+        .args(&argv[1..])
+        .envs(env_vars.iter().cloned())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .process_group(0)
+        .spawn()
+}
+
+/// Spawn the user command, logging failures to the parent via `send_log`.
+async fn spawn_child(
+    writer: &SharedWriter,
+    user_cmd: &str,
+    env_vars: &[(String, String)],
+) -> Option<tokio::process::Child> {
+    match spawn_shell(user_cmd, env_vars).await {
+        Ok(c) => Some(c),
+        Err(e) => {
+            send_log(writer, "error", &format!("Failed to start user app: {e}")).await;
+            None
+        }
+    }
+}
+
+/// Stream the child's stdout/stderr to the parent as log messages, returning
+/// the background tasks so shutdown can wait for them to drain.
+fn attach_streams(writer: &SharedWriter, child: &mut tokio::process::Child) -> Vec<JoinHandle<()>> {
+    let mut tasks = Vec::new();
 
-    // Stream stdout
     if let Some(stdout) = child.stdout.take() {
         let w = writer.clone();
-        tokio::spawn(async move {
+        tasks.push(tokio::spawn(async move {
             let reader = BufReader::new(stdout);
             let mut lines = reader.lines();
             while let Ok(Some(line)) = lines.next_line().await {
@@ -118,13 +401,12 @@ pub async fn run(
                     send_log(&w, "app", &line).await;
                 }
             }
-        });
+        }));
     }
 
-    // Stream stderr
     if let Some(stderr) = child.stderr.take() {
         let w = writer.clone();
-        tokio::spawn(async move {
+        tasks.push(tokio::spawn(async move {
             let reader = BufReader::new(stderr);
             let mut lines = reader.lines();
             while let Ok(Some(line)) = lines.next_line().await {
@@ -132,36 +414,25 @@ pub async fn run(
                     send_log(&w, "app_err", &line).await;
                 }
             }
-        });
+        }));
     }
 
-    match workload_type {
-        "batch" => run_batch(&writer, &mut child, &mut shutdown).await,
-        "service" | "daemon" => {
-            run_service(&writer, &mut child, health_interval, &mut shutdown).await
-        }
-        other => {
-            send_log(&writer, "warn", &format!("Unknown workload type '{other}', treating as batch")).await;
-            run_batch(&writer, &mut child, &mut shutdown).await;
-        }
-    }
-
-    // Ensure shutdown is signaled
-    let _ = shutdown_tx.send(true);
+    tasks
 }
 
 async fn run_batch(
     writer: &SharedWriter,
-    child: &mut tokio::process::Child,
+    managed: &mut ManagedChild,
     shutdown: &mut watch::Receiver<bool>,
 ) {
     tokio::select! {
-        result = child.wait() => {
+        result = managed.child.wait() => {
             match result {
                 Ok(status) => {
                     let code = status.code().unwrap_or(-1);
                     send_log(writer, "info", &format!("Application exited with code {code}")).await;
-                    send_health_report(writer, "completed", Some(code), "batch").await;
+                    send_health_report(writer, "completed", Some(code), "batch", None).await;
+                    drain_streams(std::mem::take(&mut managed.stream_tasks)).await;
                     // Give time for logs to flush
                     tokio::time::sleep(Duration::from_secs(5)).await;
                 }
@@ -172,33 +443,130 @@ async fn run_batch(
         }
         _ = wait_shutdown(shutdown) => {
             send_log(writer, "info", "Shutdown signal received, terminating process").await;
-            let _ = child.kill().await;
+            let kind = graceful_terminate(writer, &mut managed.child, "application").await;
+            send_health_report(writer, kind.health_status(), None, "batch", None).await;
+            drain_streams(std::mem::take(&mut managed.stream_tasks)).await;
         }
     }
 }
 
 async fn run_service(
     writer: &SharedWriter,
-    child: &mut tokio::process::Child,
+    managed: &mut ManagedChild,
+    user_cmd: &str,
+    env_vars: &[(String, String)],
+    pty_mode: bool,
+    pty_handle: &PtyHandle,
     health_interval: u64,
     shutdown: &mut watch::Receiver<bool>,
 ) {
+    let policy = RestartPolicy::from_env();
+    let max_attempts = restart_max();
+    let backoff_base_ms = restart_backoff_base_ms();
+    let mut attempt: u32 = 0;
+    let mut started_at = tokio::time::Instant::now();
+
+    let probe = Probe::from_env();
+    let liveness_threshold = liveness_failure_threshold();
+    let mut ready = probe.is_none();
+    let mut consecutive_failures: u32 = 0;
+
     let mut interval = tokio::time::interval(Duration::from_secs(health_interval));
 
     loop {
         tokio::select! {
             _ = interval.tick() => {
                 // Check if process is still running
-                match child.try_wait() {
+                match managed.child.try_wait() {
                     Ok(Some(status)) => {
-                        let code = status.code().unwrap_or(-1);
-                        send_log(writer, "error", &format!("Service exited unexpectedly with code {code}")).await;
-                        send_health_report(writer, "crashed", Some(code), "service").await;
-                        return;
+                        let code = status.code();
+
+                        if started_at.elapsed() >= RESTART_STABILITY_WINDOW {
+                            attempt = 0;
+                        }
+
+                        if policy.allows(code) && attempt < max_attempts {
+                            attempt += 1;
+                            let delay_ms = backoff_base_ms
+                                .saturating_mul(1u64 << (attempt - 1).min(32))
+                                .min(RESTART_BACKOFF_CAP_MS);
+                            send_log(
+                                writer,
+                                "error",
+                                &format!(
+                                    "Service exited unexpectedly with code {}, restarting (attempt {attempt}/{max_attempts}) in {delay_ms}ms",
+                                    code.unwrap_or(-1)
+                                ),
+                            ).await;
+                            send_health_report(writer, "restarting", code, "service", Some(json!({"attempt": attempt}))).await;
+                            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+
+                            match spawn_and_attach(writer, user_cmd, env_vars, pty_mode, pty_handle).await {
+                                Some(new_managed) => {
+                                    *managed = new_managed;
+                                    started_at = tokio::time::Instant::now();
+                                    ready = probe.is_none();
+                                    consecutive_failures = 0;
+                                }
+                                None => {
+                                    send_health_report(writer, "crashed", code, "service", Some(json!({"attempt": attempt}))).await;
+                                    return;
+                                }
+                            }
+                        } else {
+                            send_log(writer, "error", &format!("Service exited unexpectedly with code {}", code.unwrap_or(-1))).await;
+                            send_health_report(writer, "crashed", code, "service", Some(json!({"attempt": attempt}))).await;
+                            return;
+                        }
                     }
                     Ok(None) => {
-                        // Still running, send health report
-                        send_health_report(writer, "running", None, "service").await;
+                        let Some(probe) = &probe else {
+                            send_health_report(writer, "running", None, "service", None).await;
+                            continue;
+                        };
+
+                        if !ready {
+                            if probe.check().await {
+                                ready = true;
+                                send_health_report(writer, "ready", None, "service", None).await;
+                            } else if started_at.elapsed() >= READINESS_GRACE {
+                                send_health_report(writer, "unready", None, "service", None).await;
+                            }
+                            continue;
+                        }
+
+                        if probe.check().await {
+                            consecutive_failures = 0;
+                            send_health_report(writer, "running", None, "service", None).await;
+                        } else {
+                            consecutive_failures += 1;
+                            send_log(
+                                writer,
+                                "warn",
+                                &format!("Liveness probe failed ({consecutive_failures}/{liveness_threshold})"),
+                            ).await;
+
+                            if consecutive_failures >= liveness_threshold {
+                                send_health_report(
+                                    writer,
+                                    "unhealthy",
+                                    None,
+                                    "service",
+                                    Some(json!({"consecutive_failures": consecutive_failures})),
+                                ).await;
+                                // Don't return here: the restart-policy branch above picks
+                                // the exit up on the next tick once the child actually exits.
+                                graceful_terminate(writer, &mut managed.child, "unhealthy service").await;
+                            } else {
+                                send_health_report(
+                                    writer,
+                                    "running",
+                                    None,
+                                    "service",
+                                    Some(json!({"consecutive_failures": consecutive_failures})),
+                                ).await;
+                            }
+                        }
                     }
                     Err(e) => {
                         send_log(writer, "error", &format!("Failed to check process: {e}")).await;
@@ -207,14 +575,22 @@ async fn run_service(
             }
             _ = wait_shutdown(shutdown) => {
                 send_log(writer, "info", "Shutdown signal received, terminating service").await;
-                let _ = child.kill().await;
+                let kind = graceful_terminate(writer, &mut managed.child, "service").await;
+                send_health_report(writer, kind.health_status(), None, "service", None).await;
+                drain_streams(std::mem::take(&mut managed.stream_tasks)).await;
                 return;
             }
         }
     }
 }
 
-async fn send_health_report(writer: &SharedWriter, status: &str, exit_code: Option<i32>, workload_type: &str) {
+async fn send_health_report(
+    writer: &SharedWriter,
+    status: &str,
+    exit_code: Option<i32>,
+    workload_type: &str,
+    extra: Option<Value>,
+) {
     let mut payload = json!({
         "status": status,
         "workload_type": workload_type,
@@ -222,6 +598,11 @@ async fn send_health_report(writer: &SharedWriter, status: &str, exit_code: Opti
     if let Some(code) = exit_code {
         payload["exit_code"] = json!(code);
     }
+    if let Some(Value::Object(extra)) = extra {
+        if let Value::Object(base) = &mut payload {
+            base.extend(extra);
+        }
+    }
 
     let msg = Message {
         msg_type: "health_report".to_string(),